@@ -3,7 +3,8 @@
 use std::{
     cell::RefCell,
     collections::{HashMap, VecDeque},
-    rc::Rc,
+    fmt,
+    rc::{Rc, Weak},
 };
 
 /// The input to the puzzle
@@ -43,41 +44,42 @@ const CD: &str = "cd";
 const LS: &str = "ls";
 /// The parent directory context, for convenience.
 const PARENT_DIR: &str = "..";
+/// The root directory context, for convenience.
+const ROOT_DIR: &str = "/";
+/// The `mkdir` command, for convenience.
+const MKDIR: &str = "mkdir";
+/// The `touch` command, for convenience.
+const TOUCH: &str = "touch";
+/// The `rm` command, for convenience.
+const RM: &str = "rm";
 
-/// The [Command] struct represents a command that was ran as well as its output.
+/// The [Command] struct represents a command that was run, its arguments, and its output.
 #[derive(Debug)]
 struct Command {
-    /// The command that was executed.
-    kind: CommandKind,
+    /// The literal name of the command that was executed, e.g. `cd` or `mkdir`.
+    name: String,
+    /// The arguments passed to the command, in order.
+    args: Vec<String>,
     /// The output of the command, including both stderr and stdout, split by newlines.
     /// No distinction is made between stdout and stderr because the puzzle doesn't require it.
     output: Vec<String>,
 }
 
-/// Our puzzle input only features 2 commands, `cd` and `ls`. `cd` will have an argument
-/// that is the path to the directory to change to, and `ls` will always have no arguments.
-#[derive(Debug)]
-enum CommandKind {
-    Cd(String),
-    Ls,
-}
-
-impl TryFrom<String> for CommandKind {
+impl TryFrom<String> for Command {
     type Error = &'static str;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
         // Commands are structured as: `$ <command> [args]`
-        let split = value.split_whitespace().collect::<Vec<&str>>();
-        match *split.get(1).ok_or("Failed to parse command")? {
-            CD => Ok(Self::Cd(
-                split
-                    .get(2)
-                    .ok_or("Failed to parse command arguments")?
-                    .to_string(),
-            )),
-            LS => Ok(Self::Ls),
-            _ => Err("Invalid command"),
-        }
+        let mut split = value.split_whitespace();
+        split.next().ok_or("Failed to parse command")?;
+        let name = split.next().ok_or("Failed to parse command")?.to_string();
+        let args = split.map(str::to_string).collect();
+
+        Ok(Self {
+            name,
+            args,
+            output: Vec::default(),
+        })
     }
 }
 
@@ -89,6 +91,16 @@ struct FSEntry {
     pub children: Option<Vec<SharedFSEntry>>,
     /// The size of the file on disk. If this is a directory, this will be `None`.
     implicit_size: Option<usize>,
+    /// The parent directory of this [FSEntry]. `None` for the root.
+    ///
+    /// This is a [Weak] reference rather than a [SharedFSEntry] so that the tree doesn't
+    /// contain reference cycles; children never keep their parent alive, and a parent
+    /// stays alive only as long as it is reachable from the root.
+    parent: Option<Weak<RefCell<FSEntry>>>,
+    /// Whether `ls` has already been run on this directory. `mkdir`/`touch` can populate
+    /// `children` without this being true, so [ls_handler] checks this flag directly
+    /// instead of inferring "already listed" from `children` being non-empty.
+    listed: bool,
 }
 
 /// A [SharedFSEntry] is an [FSEntry] that can be shared between multiple owners
@@ -100,11 +112,14 @@ impl FSEntry {
         name: String,
         children: Option<Vec<SharedFSEntry>>,
         implicit_size: Option<usize>,
+        parent: Option<Weak<RefCell<FSEntry>>>,
     ) -> Self {
         Self {
             name,
             children,
             implicit_size,
+            parent,
+            listed: false,
         }
     }
 
@@ -132,6 +147,109 @@ impl FSEntry {
             None => 0,
         }
     }
+
+    /// Finds the size of the smallest descendant directory (including `self`) whose
+    /// [FSEntry::size] is `>= needed`, if one exists.
+    fn deletable_size(&self, needed: usize) -> Option<usize> {
+        let self_size = self.size();
+        let candidate = (self_size >= needed).then_some(self_size);
+
+        match self.children {
+            Some(ref children) => children
+                .iter()
+                .map(|c| c.borrow())
+                .filter(|c| c.children.is_some())
+                .filter_map(|c| c.deletable_size(needed))
+                .chain(candidate)
+                .min(),
+            None => candidate,
+        }
+    }
+
+    /// Walks the tree and returns every directory's cumulative file size keyed by its
+    /// absolute path (e.g. `/a/e`), with the root's total stored under the empty path `""`.
+    fn dir_sizes(&self) -> HashMap<String, usize> {
+        let mut sizes = HashMap::default();
+        self.collect_dir_sizes("", &mut sizes);
+        sizes
+    }
+
+    /// Recursive helper for [FSEntry::dir_sizes]. Builds bottom-up: each child directory's
+    /// total is computed by recursing into it exactly once, and `self`'s total is the sum of
+    /// its direct file sizes plus those child totals, rather than calling [FSEntry::size]
+    /// (which would re-walk the whole subtree at every node). Returns `self`'s total so the
+    /// caller one level up can fold it in without looking it back up in `sizes`.
+    fn collect_dir_sizes(&self, path: &str, sizes: &mut HashMap<String, usize>) -> usize {
+        let Some(children) = &self.children else {
+            return self.size();
+        };
+
+        let total = children
+            .iter()
+            .map(|c| {
+                let child = c.borrow();
+                if child.children.is_some() {
+                    let child_path = format!("{path}/{}", child.name);
+                    child.collect_dir_sizes(&child_path, sizes)
+                } else {
+                    child.size()
+                }
+            })
+            .sum();
+
+        sizes.insert(path.to_string(), total);
+        total
+    }
+
+    /// Recursively writes an indented tree listing of `self` and its descendants, indenting
+    /// each level by two spaces per `depth`. Returns `self`'s total size so that a
+    /// directory's children are rendered into a buffer first and their sizes summed from
+    /// that single pass, rather than calling [FSEntry::size] at every node (which would
+    /// re-walk the whole subtree each time, the same O(n^2) pattern [FSEntry::dir_sizes]
+    /// avoids).
+    fn render(&self, out: &mut impl fmt::Write, depth: usize) -> Result<usize, fmt::Error> {
+        let indent = "  ".repeat(depth);
+        match &self.children {
+            Some(children) => {
+                let mut body = String::new();
+                let mut total = 0;
+                for c in children {
+                    total += c.borrow().render(&mut body, depth + 1)?;
+                }
+                writeln!(out, "{indent}- {} (dir, size={})", self.name, total)?;
+                out.write_str(&body)?;
+                Ok(total)
+            }
+            None => {
+                let size = self.size();
+                writeln!(out, "{indent}- {} (file, size={})", self.name, size)?;
+                Ok(size)
+            }
+        }
+    }
+}
+
+impl fmt::Display for FSEntry {
+    /// Pretty-prints the tree rooted at `self`, indenting each nested level. Useful for
+    /// inspecting parser output, since `#[derive(Debug)]` is unreadable for nested
+    /// `Rc<RefCell<..>>` trees.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(f, 0).map(|_| ())
+    }
+}
+
+/// The total disk capacity available, in bytes, as specified by the puzzle.
+const TOTAL_DISK_SPACE: usize = 70_000_000;
+/// The amount of free space required to run the update, in bytes, as specified by the puzzle.
+const REQUIRED_FREE_SPACE: usize = 30_000_000;
+
+/// Solves Part 2: finds the size of the smallest directory that, if deleted, would free up
+/// enough space to leave [REQUIRED_FREE_SPACE] bytes free out of [TOTAL_DISK_SPACE].
+fn smallest_deletable_dir(root: &FSEntry) -> Option<usize> {
+    let used = root.size();
+    let free = TOTAL_DISK_SPACE.saturating_sub(used);
+    let needed = REQUIRED_FREE_SPACE.saturating_sub(free);
+    root.deletable_size(needed)
 }
 
 /// Reads the puzzle input and returns a vector of [Command]s, which can then
@@ -167,15 +285,274 @@ fn lex(input: &str) -> Result<VecDeque<Command>, &'static str> {
             output.push(lines.pop_front().ok_or("Failed to pop line from dequeue")?);
         }
 
-        commands.push_back(Command {
-            kind: command.try_into()?,
-            output,
-        });
+        let mut command: Command = command.try_into()?;
+        command.output = output;
+        commands.push_back(command);
     }
 
     Ok(commands)
 }
 
+/// A borrowed counterpart to [Command] that reuses `&str` slices from the input rather
+/// than allocating a `String` per line. Produced by [lex_iter] for zero-copy parsing of
+/// large transcripts.
+#[derive(Debug)]
+struct BorrowedCommand<'a> {
+    /// The literal name of the command that was executed, e.g. `cd` or `mkdir`.
+    name: &'a str,
+    /// The arguments passed to the command, in order.
+    args: Vec<&'a str>,
+    /// The output of the command, split by newlines.
+    output: Vec<&'a str>,
+}
+
+/// Like [lex], but returns an iterator that borrows `&str` slices from `input` instead of
+/// allocating a `String` per line, and yields one [BorrowedCommand] at a time as it scans
+/// rather than materializing the whole command list up front. This gives callers a
+/// zero-copy path for large transcripts; [build_fs_iter] drives the same registered
+/// handlers as [build_fs] straight off of this stream, without ever holding the full
+/// command list in memory.
+fn lex_iter(input: &str) -> impl Iterator<Item = Result<BorrowedCommand<'_>, &'static str>> {
+    let mut lines = input.split(NEWLINE).peekable();
+
+    std::iter::from_fn(move || {
+        let command_line = lines.next()?;
+        let mut split = command_line.split_whitespace();
+
+        Some((|| {
+            split.next().ok_or("Failed to parse command")?;
+            let name = split.next().ok_or("Failed to parse command")?;
+            let args = split.collect::<Vec<&str>>();
+
+            let mut output = Vec::default();
+            while let Some(line) = lines.peek() {
+                if line.starts_with(CMD_DELIMITER) {
+                    break;
+                }
+                output.push(lines.next().expect("a peeked line is always present"));
+            }
+
+            Ok(BorrowedCommand { name, args, output })
+        })())
+    })
+}
+
+/// The state threaded through every registered command: the node currently being
+/// operated on plus the tree's root, so that commands like `cd /` can reach either
+/// without any external bookkeeping.
+struct FsState {
+    /// The directory the next command operates relative to.
+    current: SharedFSEntry,
+    /// The root of the tree, kept around so `cd /` can jump back to it from any depth.
+    root: SharedFSEntry,
+    /// The output lines attached to the command currently being dispatched. Only `ls`
+    /// makes use of this; other handlers only need their `args`.
+    output: Vec<String>,
+}
+
+/// A handler mutates [FsState] in response to a single command's `args`.
+type CommandHandler = Box<dyn Fn(&mut FsState, &[&str]) -> Result<(), &'static str>>;
+
+/// A [CommandRegistry] maps literal command names (`cd`, `ls`, `mkdir`, ...) to the
+/// handlers that execute them, inspired by Brigadier-style command registration. New
+/// commands can be added by registering a closure under a name, without touching the
+/// dispatch loop in [build_fs].
+#[derive(Default)]
+struct CommandRegistry {
+    handlers: HashMap<String, CommandHandler>,
+}
+
+impl CommandRegistry {
+    /// Registers `handler` to run whenever a command named `name` is dispatched.
+    fn register(
+        &mut self,
+        name: &str,
+        handler: impl Fn(&mut FsState, &[&str]) -> Result<(), &'static str> + 'static,
+    ) {
+        self.handlers.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Looks up the handler registered for `name` and runs it with `args` against `state`.
+    fn dispatch(&self, state: &mut FsState, name: &str, args: &[&str]) -> Result<(), &'static str> {
+        let handler = self.handlers.get(name).ok_or("Unregistered command")?;
+        handler(state, args)
+    }
+
+    /// Builds a registry with the `cd`, `ls`, `mkdir`, `touch`, and `rm` builtins registered.
+    fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        registry.register(CD, cd_handler);
+        registry.register(LS, ls_handler);
+        registry.register(MKDIR, mkdir_handler);
+        registry.register(TOUCH, touch_handler);
+        registry.register(RM, rm_handler);
+        registry
+    }
+}
+
+/// Handles `cd <name>`, following the parent link for `..`, jumping to the stored root
+/// for `/`, and otherwise reusing or creating a child directory of `state.current`.
+fn cd_handler(state: &mut FsState, args: &[&str]) -> Result<(), &'static str> {
+    let dir_name = *args.first().ok_or("`cd` requires a directory argument")?;
+
+    match dir_name {
+        PARENT_DIR => {
+            let parent = state
+                .current
+                .borrow()
+                .parent
+                .as_ref()
+                .and_then(Weak::upgrade)
+                .ok_or("Attempted to move up from root directory")?;
+            state.current = parent;
+        }
+        ROOT_DIR => state.current = Rc::clone(&state.root),
+        _ => {
+            // Reuse an existing child directory of this name if the transcript has
+            // already visited it, rather than unconditionally pushing a new one.
+            let existing = state
+                .current
+                .borrow()
+                .children
+                .as_ref()
+                .and_then(|children| {
+                    children
+                        .iter()
+                        .find(|c| {
+                            let c = c.borrow();
+                            c.name == dir_name && c.children.is_some()
+                        })
+                        .cloned()
+                });
+
+            state.current = match existing {
+                Some(child) => child,
+                None => {
+                    let new_context = Rc::new(RefCell::new(FSEntry::new(
+                        dir_name.to_string(),
+                        Some(Vec::default()),
+                        None,
+                        Some(Rc::downgrade(&state.current)),
+                    )));
+                    if let Some(ref mut children) = state.current.borrow_mut().children {
+                        children.push(Rc::clone(&new_context));
+                    }
+                    new_context
+                }
+            };
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles `ls`, populating `state.current`'s children from `state.output`.
+///
+/// If `state.current` has already been listed, this is a no-op: a transcript that `cd`s
+/// back into a directory and `ls`es it again describes the same listing, and re-populating
+/// would double up every entry. This is tracked via [FSEntry::listed] rather than inferred
+/// from `children` being non-empty, since `mkdir`/`touch` can populate `children` without
+/// the directory ever having been `ls`'d.
+fn ls_handler(state: &mut FsState, _args: &[&str]) -> Result<(), &'static str> {
+    if state.current.borrow().listed {
+        return Ok(());
+    }
+
+    let parent = Rc::downgrade(&state.current);
+    let output = std::mem::take(&mut state.output);
+
+    if let Some(ref mut children) = state.current.borrow_mut().children {
+        for o in output {
+            // Split the output by whitespace to parse the file size and name.
+            let split = o.split_whitespace().collect::<Vec<&str>>();
+            // The file size is the first element in the split.
+            let size = split
+                .first()
+                .ok_or("Failed to parse file size from `ls` output")?
+                .parse::<usize>()
+                .ok();
+            // The file name is the second element in the split.
+            let name = split
+                .get(1)
+                .ok_or("Failed to parse file name from `ls` output")?
+                .to_string();
+            // Allocate a vec for the child if it's a directory.
+            let child_vec = if size.is_some() {
+                None
+            } else {
+                Some(Vec::default())
+            };
+
+            // Create the child and add it to the current context's children.
+            children.push(Rc::new(RefCell::new(FSEntry::new(
+                name,
+                child_vec,
+                size,
+                Some(parent.clone()),
+            ))));
+        }
+    }
+
+    state.current.borrow_mut().listed = true;
+
+    Ok(())
+}
+
+/// Handles `mkdir <name>`, creating an empty subdirectory of `state.current` unless an
+/// entry of that name (file or directory) already exists, in which case nothing changes.
+fn mkdir_handler(state: &mut FsState, args: &[&str]) -> Result<(), &'static str> {
+    let name = *args.first().ok_or("`mkdir` requires a name argument")?;
+    let parent = Rc::downgrade(&state.current);
+
+    if let Some(ref mut children) = state.current.borrow_mut().children {
+        if !children.iter().any(|c| c.borrow().name == name) {
+            children.push(Rc::new(RefCell::new(FSEntry::new(
+                name.to_string(),
+                Some(Vec::default()),
+                None,
+                Some(parent),
+            ))));
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles `touch <name> <size>`, replacing any existing entry of that name in
+/// `state.current` with a file of the given size.
+fn touch_handler(state: &mut FsState, args: &[&str]) -> Result<(), &'static str> {
+    let name = *args.first().ok_or("`touch` requires a name argument")?;
+    let size = args
+        .get(1)
+        .ok_or("`touch` requires a size argument")?
+        .parse::<usize>()
+        .map_err(|_| "Failed to parse `touch` size argument")?;
+    let parent = Rc::downgrade(&state.current);
+
+    if let Some(ref mut children) = state.current.borrow_mut().children {
+        children.retain(|c| c.borrow().name != name);
+        children.push(Rc::new(RefCell::new(FSEntry::new(
+            name.to_string(),
+            None,
+            Some(size),
+            Some(parent),
+        ))));
+    }
+
+    Ok(())
+}
+
+/// Handles `rm <name>`, removing the named entry from `state.current`'s children.
+fn rm_handler(state: &mut FsState, args: &[&str]) -> Result<(), &'static str> {
+    let name = *args.first().ok_or("`rm` requires a name argument")?;
+
+    if let Some(ref mut children) = state.current.borrow_mut().children {
+        children.retain(|c| c.borrow().name != name);
+    }
+
+    Ok(())
+}
+
 /// To convert a vector of [Command]s into a [FSEntry], we need to build the file tree.
 /// A convenient way to do this is by performing a depth-first search to ensure that
 /// we can resolve the directory sizes upwards.
@@ -184,89 +561,74 @@ fn lex(input: &str) -> Result<VecDeque<Command>, &'static str> {
 fn build_fs(mut cmds: VecDeque<Command>) -> Result<SharedFSEntry, &'static str> {
     // Create the root [FSEntry]. The first command in the list must be `cd`, as defined
     // by the puzzle input.
-    let root_context = Rc::new(RefCell::new(match cmds.pop_front() {
-        Some(Command {
-            kind: CommandKind::Cd(dir_name),
-            ..
-        }) => Ok(FSEntry::new(dir_name, Some(Vec::default()), None)),
+    let root = Rc::new(RefCell::new(match cmds.pop_front() {
+        Some(Command { name, args, .. }) if name == CD => {
+            let dir_name = args.first().ok_or("Failed to parse command arguments")?;
+            Ok(FSEntry::new(
+                dir_name.clone(),
+                Some(Vec::default()),
+                None,
+                None,
+            ))
+        }
         _ => Err("First command is not `cd`"),
     }?));
 
-    // Keep track of the current and previous contexts.
-    let mut current_context = Rc::clone(&root_context);
-
-    // Keep track of directories at each depth.
-    let mut depth = 0;
-    let mut entries_at_depth = HashMap::<usize, SharedFSEntry>::default();
-    entries_at_depth.insert(depth, Rc::clone(&root_context));
+    let registry = CommandRegistry::with_builtins();
+    let mut state = FsState {
+        current: Rc::clone(&root),
+        root: Rc::clone(&root),
+        output: Vec::default(),
+    };
 
-    // Iterate over the remaining commands and build the file tree.
+    // Iterate over the remaining commands, dispatching each to its registered handler.
     while let Some(command) = cmds.pop_front() {
-        match command.kind {
-            CommandKind::Cd(dir_name) => match dir_name.as_str() {
-                // Move up a directory. We can do this by setting the current context
-                // to the parent of the current context.
-                PARENT_DIR => match depth {
-                    0 => return Err("Attempted to move up from root directory"),
-                    _ => {
-                        depth -= 1;
-                        current_context = Rc::clone(
-                            entries_at_depth
-                                .get(&depth)
-                                .ok_or("Failed to get entry at depth")?,
-                        )
-                    }
-                },
-                _ => {
-                    // Create a new subdirectory and increment the depth.
-                    // Note that this behavior only considers that an unknown directory is a
-                    // child of the current context. This is valid in the context of the AoC
-                    // puzzle, but not in general.
-                    let new_context = Rc::new(RefCell::new(FSEntry::new(
-                        dir_name.clone(),
-                        Some(Vec::default()),
-                        None,
-                    )));
-                    if let Some(ref mut children) = current_context.borrow_mut().children {
-                        children.push(Rc::clone(&new_context));
-                        depth += 1;
-                        entries_at_depth.insert(depth, Rc::clone(&new_context));
-                    }
-                    current_context = new_context;
-                }
-            },
-            CommandKind::Ls => {
-                if let Some(ref mut children) = current_context.borrow_mut().children {
-                    for o in command.output {
-                        // Split the output by whitespace to parse the file size and name.
-                        let split = o.split_whitespace().collect::<Vec<&str>>();
-                        // The file size is the first element in the split.
-                        let size = split
-                            .first()
-                            .ok_or("Failed to parse file size from `ls` output")?
-                            .parse::<usize>()
-                            .ok();
-                        // The file name is the second element in the split.
-                        let name = split
-                            .get(1)
-                            .ok_or("Failed to parse file name from `ls` output")?
-                            .to_string();
-                        // Allocate a vec for the child if it's a directory.
-                        let child_vec = if size.is_some() {
-                            None
-                        } else {
-                            Some(Vec::default())
-                        };
-
-                        // Create the child and add it to the current context's children.
-                        children.push(Rc::new(RefCell::new(FSEntry::new(name, child_vec, size))));
-                    }
-                }
-            }
+        state.output = command.output;
+        let args = command.args.iter().map(String::as_str).collect::<Vec<_>>();
+        registry.dispatch(&mut state, &command.name, &args)?;
+    }
+
+    Ok(root)
+}
+
+/// Like [build_fs], but drives the same registered handlers straight off of a
+/// [lex_iter]-style stream of [BorrowedCommand]s instead of a pre-built [VecDeque],
+/// so large transcripts never need their full command list materialized in memory.
+fn build_fs_iter<'a>(
+    mut cmds: impl Iterator<Item = Result<BorrowedCommand<'a>, &'static str>>,
+) -> Result<SharedFSEntry, &'static str> {
+    // Create the root [FSEntry]. The first command in the stream must be `cd`, as defined
+    // by the puzzle input.
+    let root = Rc::new(RefCell::new(match cmds.next() {
+        Some(Ok(BorrowedCommand { name, args, .. })) if name == CD => {
+            let dir_name = args.first().ok_or("Failed to parse command arguments")?;
+            Ok(FSEntry::new(
+                dir_name.to_string(),
+                Some(Vec::default()),
+                None,
+                None,
+            ))
         }
+        Some(Err(e)) => Err(e),
+        _ => Err("First command is not `cd`"),
+    }?));
+
+    let registry = CommandRegistry::with_builtins();
+    let mut state = FsState {
+        current: Rc::clone(&root),
+        root: Rc::clone(&root),
+        output: Vec::default(),
+    };
+
+    // Iterate over the remaining commands, dispatching each to its registered handler as
+    // it's pulled off the stream.
+    for command in cmds {
+        let command = command?;
+        state.output = command.output.iter().map(|s| s.to_string()).collect();
+        registry.dispatch(&mut state, command.name, &command.args)?;
     }
 
-    Ok(root_context)
+    Ok(root)
 }
 
 #[cfg(test)]
@@ -276,6 +638,9 @@ mod test {
     /// The magic number provided by AoC 7 as the solution to the given puzzle input.
     const MAGIC_NUMBER: usize = 95437;
 
+    /// The magic number provided by AoC 7 as the solution to the Part 2 puzzle input.
+    const MAGIC_NUMBER_PART_2: usize = 24933642;
+
     #[test]
     fn test_solution() {
         let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
@@ -283,4 +648,143 @@ mod test {
 
         assert_eq!(fs.borrow().prunable_size(), MAGIC_NUMBER);
     }
+
+    #[test]
+    fn test_dir_sizes() {
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        let sizes = fs.borrow().dir_sizes();
+        assert_eq!(sizes.get(""), Some(&48381165));
+        assert_eq!(sizes.get("/a"), Some(&94853));
+        assert_eq!(sizes.get("/a/e"), Some(&584));
+        assert_eq!(sizes.get("/d"), Some(&24933642));
+    }
+
+    #[test]
+    fn test_display() {
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        let rendered = fs.borrow().to_string();
+        assert!(rendered.starts_with("- / (dir, size=48381165)\n"));
+        assert!(rendered.contains("  - a (dir, size=94853)\n"));
+        assert!(rendered.contains("    - e (dir, size=584)\n"));
+        assert!(rendered.contains("      - i (file, size=584)\n"));
+    }
+
+    #[test]
+    fn test_ls_revisit_is_idempotent() {
+        // `a` is entered, listed, left, and re-entered and re-listed. The second `ls`
+        // describes the same directory and must not double its entries.
+        let transcript = "$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n10 f\n$ cd ..\n$ cd a\n$ ls\n10 f\n";
+        let fs = build_fs(lex(transcript).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        assert_eq!(fs.borrow().dir_sizes().get("/a"), Some(&10));
+    }
+
+    #[test]
+    fn test_ls_after_mkdir_touch_is_not_mistaken_for_already_listed() {
+        // `a` is created and populated via `mkdir`/`touch` before it is ever `ls`'d. The
+        // first real `ls` must still take effect, even though `children` is already
+        // non-empty by the time it runs.
+        let transcript = "$ cd /\n$ mkdir a\n$ cd a\n$ touch f1 5\n$ cd ..\n$ cd a\n$ ls\n10 f2";
+        let fs = build_fs(lex(transcript).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        assert_eq!(fs.borrow().dir_sizes().get("/a"), Some(&15));
+    }
+
+    #[test]
+    fn test_lex_iter_matches_lex() {
+        let eager = lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail");
+        let streamed = lex_iter(PUZZLE_INPUT.trim())
+            .collect::<Result<Vec<_>, _>>()
+            .expect("Streaming lex should not fail");
+
+        assert_eq!(eager.len(), streamed.len());
+        for (owned, borrowed) in eager.iter().zip(streamed.iter()) {
+            assert_eq!(owned.name, borrowed.name);
+            assert_eq!(owned.args, borrowed.args);
+            assert_eq!(owned.output, borrowed.output);
+        }
+    }
+
+    #[test]
+    fn test_build_fs_iter_matches_build_fs() {
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+        let fs_iter = build_fs_iter(lex_iter(PUZZLE_INPUT.trim()))
+            .expect("Building the file system DAG from a stream should not fail");
+
+        assert_eq!(fs.borrow().size(), fs_iter.borrow().size());
+        assert_eq!(fs.borrow().to_string(), fs_iter.borrow().to_string());
+    }
+
+    #[test]
+    fn test_registered_mkdir_touch_rm() {
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        let registry = CommandRegistry::with_builtins();
+        let mut state = FsState {
+            current: Rc::clone(&fs),
+            root: Rc::clone(&fs),
+            output: Vec::default(),
+        };
+
+        registry
+            .dispatch(&mut state, MKDIR, &["z"])
+            .expect("mkdir should succeed");
+        registry
+            .dispatch(&mut state, CD, &["z"])
+            .expect("cd into new directory should succeed");
+        registry
+            .dispatch(&mut state, TOUCH, &["w", "100"])
+            .expect("touch should succeed");
+
+        assert_eq!(fs.borrow().size(), 48381165 + 100);
+
+        registry
+            .dispatch(&mut state, RM, &["w"])
+            .expect("rm should succeed");
+
+        assert_eq!(fs.borrow().size(), 48381165);
+    }
+
+    #[test]
+    fn test_solution_part_2() {
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        assert_eq!(
+            smallest_deletable_dir(&fs.borrow()),
+            Some(MAGIC_NUMBER_PART_2)
+        );
+    }
+
+    #[test]
+    fn test_solution_part_2_does_not_panic_when_usage_exceeds_disk_space() {
+        // A tree built past `TOTAL_DISK_SPACE` (e.g. via the `mkdir`/`touch` dispatcher)
+        // must not panic on subtraction overflow when computing how much space is needed.
+        let fs = build_fs(lex(PUZZLE_INPUT.trim()).expect("Lexing should not fail"))
+            .expect("Building the file system DAG should not fail");
+
+        let registry = CommandRegistry::with_builtins();
+        let mut state = FsState {
+            current: Rc::clone(&fs),
+            root: Rc::clone(&fs),
+            output: Vec::default(),
+        };
+        registry
+            .dispatch(&mut state, TOUCH, &["huge", "90000000"])
+            .expect("touch should succeed");
+
+        // No subdirectory is large enough to free the required space on its own, so the
+        // only deletable candidate is the root itself; the important thing is that this
+        // doesn't panic.
+        let root_size = fs.borrow().size();
+        assert_eq!(smallest_deletable_dir(&fs.borrow()), Some(root_size));
+    }
 }